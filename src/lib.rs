@@ -1,6 +1,3 @@
-#![feature(proc_macro_diagnostic)]
-#![feature(allow_internal_unstable)]
-
 //! A macro like lazy_static can initialize static variables.
 //!
 //! # Usage
@@ -13,12 +10,19 @@
 //! fn main() {
 //!    println!("{}",NAME.deref());
 //! }
-//!  
+//!
 //! ```
+//!
+//! To declare several lazy statics at once, use [`macro@lazy_block`] instead
+//! of repeating `#[lazy]`. It's intentionally not named `lazy!`: Rust doesn't
+//! allow an attribute macro and a function-like macro to share one item name
+//! in the same crate, so `lazy!` was unavailable and `lazy_block!` is the
+//! crate's public name for this form — not a placeholder pending a rename.
 
 use proc_macro::TokenStream;
 use quote::{quote, quote_spanned};
 use syn::parse::{Parse, ParseStream, Result};
+use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
 use syn::{parse_macro_input, Expr, Ident, Token, Type, Visibility};
 
@@ -33,6 +37,12 @@ impl Parse for LazyStatic {
     fn parse(input: ParseStream) -> Result<Self> {
         let visibility: Visibility = input.parse()?;
         input.parse::<Token![static]>()?;
+        // `lazy_static!` spells declarations as `static ref NAME: Type = expr;`;
+        // accept that `ref` too. Only `lazy_block!` can actually make use of
+        // this: an attribute macro's item still has to parse as a *real*
+        // Rust item before the attribute runs, and `ref` isn't valid static
+        // syntax, so rustc rejects `#[lazy] static ref NAME: ...;` outright.
+        input.parse::<Option<Token![ref]>>()?;
         let name: Ident = input.parse()?;
         input.parse::<Token![:]>()?;
         let ty: Type = input.parse()?;
@@ -48,6 +58,60 @@ impl Parse for LazyStatic {
     }
 }
 
+/// One or more [`LazyStatic`] declarations, as accepted by `lazy_block!`.
+///
+/// Reuses the [`LazyStatic`] parser in a loop (the same thing `Punctuated`
+/// does internally), since each declaration is already self-terminated by
+/// its own `;` and there is no separator to punctuate on.
+struct LazyStatics(Vec<LazyStatic>);
+
+impl Parse for LazyStatics {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut items = Vec::new();
+        while !input.is_empty() {
+            items.push(input.parse()?);
+        }
+        Ok(LazyStatics(items))
+    }
+}
+
+/// Parameters accepted inside `#[lazy(...)]`.
+#[derive(Default)]
+struct LazyArgs {
+    /// `#[lazy(thread_local)]`: initialize once per thread instead of once
+    /// for the whole process, so the init expression needn't be `Sync`.
+    thread_local: bool,
+    /// `#[lazy(drop)]`: run the value's destructor at process exit instead
+    /// of leaking it. Requires the using crate to depend on `libc`.
+    drop: bool,
+}
+
+impl Parse for LazyArgs {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let mut args = LazyArgs::default();
+        let idents = Punctuated::<Ident, Token![,]>::parse_terminated(input)?;
+        for ident in &idents {
+            if ident == "thread_local" {
+                args.thread_local = true;
+            } else if ident == "drop" {
+                args.drop = true;
+            } else {
+                return Err(syn::Error::new(
+                    ident.span(),
+                    format!("unknown `#[lazy]` argument `{}`", ident),
+                ));
+            }
+        }
+        if args.thread_local && args.drop {
+            return Err(syn::Error::new(
+                idents.span(),
+                "`#[lazy(drop)]` cannot be combined with `#[lazy(thread_local)]`",
+            ));
+        }
+        Ok(args)
+    }
+}
+
 /// Parses the following syntax
 /// ```
 /// # const IGNORE_TOKENS: &str = stringify! {
@@ -55,6 +119,10 @@ impl Parse for LazyStatic {
 /// $Visibility static $NAME: $Type = $EXPRESS;
 /// # };
 /// ```
+/// For `lazy_static!` compatibility, `static ref NAME: ...;` is also accepted,
+/// but only through [`macro@lazy_block`]: rustc parses an attribute's item as
+/// a real `static` before handing it to the attribute, so `#[lazy] static ref
+/// NAME: ...;` can't compile here.
 /// # Example
 /// ```
 /// # const IGNORE_TOKENS: &str = stringify! {
@@ -62,35 +130,58 @@ impl Parse for LazyStatic {
 /// pub static foo: String = "Hello".to_string();
 /// # };
 /// ```
+///
+/// Pass `#[lazy(thread_local)]` to initialize the value once per thread
+/// instead of once for the whole process, or `#[lazy(drop)]` to run the
+/// value's destructor at process exit instead of leaking it. `#[lazy(drop)]`
+/// registers the teardown with libc's `atexit`, so the crate using it must
+/// add a direct `libc` dependency of its own (a proc-macro crate like this
+/// one can only export macros, not a re-exported `libc` for callers to use).
 #[proc_macro_attribute]
 pub fn lazy(attr: TokenStream, item: TokenStream) -> TokenStream {
-    if !attr.is_empty() {
-        proc_macro2::TokenStream::from(attr)
-            .span()
-            .unwrap()
-            .error("no parameter should be at here.")
-            .emit();
-        return TokenStream::new();
-    }
+    let args = match syn::parse::<LazyArgs>(attr) {
+        Ok(args) => args,
+        Err(err) => return TokenStream::from(err.to_compile_error()),
+    };
+    let item = parse_macro_input!(item as LazyStatic);
+    TokenStream::from(expand(item, args))
+}
+
+/// Declares one or more lazy statics in a single invocation, mirroring the
+/// block form of the original `lazy_static!` macro. Each entry uses the same
+/// syntax as [`macro@lazy`] and expands to the same zero-sized-struct +
+/// `Deref` machinery.
+///
+/// Not named `lazy!`: an attribute macro and a function-like macro can't
+/// share one item name in the same crate, since both live in the value
+/// namespace. `lazy_block` is this macro's permanent, settled name, not a
+/// stand-in awaiting one.
+///
+/// # Example
+/// ```
+/// # const IGNORE_TOKENS: &str = stringify! {
+/// lazy_block! {
+///     pub static FOO: String = "Hello".to_string();
+///     static ref BAR: u32 = 42;
+/// }
+/// # };
+/// ```
+#[proc_macro]
+pub fn lazy_block(input: TokenStream) -> TokenStream {
+    let LazyStatics(items) = parse_macro_input!(input as LazyStatics);
+    let expanded = items
+        .into_iter()
+        .map(|item| expand(item, LazyArgs::default()));
+    TokenStream::from(quote! { #(#expanded)* })
+}
+
+fn expand(item: LazyStatic, args: LazyArgs) -> proc_macro2::TokenStream {
     let LazyStatic {
         visibility,
         name,
         ty,
         init,
-    } = parse_macro_input!(item as LazyStatic);
-
-    // Assert that the static type implements Sync. If not, user sees an error
-    // message like the following. We span this assertion with the field type's
-    // line/column so that the error message appears in the correct place.
-    //
-    //     error[E0277]: the trait bound `*const (): std::marker::Sync` is not satisfied
-    //       --> src/main.rs:10:21
-    //        |
-    //     10 |     static ref PTR: *const () = &();
-    //        |                     ^^^^^^^^^ `*const ()` cannot be shared between threads safely
-    let assert_sync = quote_spanned! {ty.span()=>
-        struct _AssertSync where #ty: std::marker::Sync;
-    };
+    } = item;
 
     // Check for Sized. Not vital to check here, but the error message is less
     // confusing this way than if they get a Sized error in one of our
@@ -105,31 +196,156 @@ pub fn lazy(attr: TokenStream, item: TokenStream) -> TokenStream {
         struct _AssertSized where #ty: std::marker::Sized;
     };
 
+    if args.thread_local {
+        // A `thread_local!` value is initialized once per thread rather
+        // than once for the whole process, so it never needs to be shared
+        // across threads and the `Sync` assertion doesn't apply.
+        let init_ptr = quote_spanned! {init.span()=>
+            Box::into_raw(Box::new(#init))
+        };
+
+        return quote! {
+            #[allow(non_camel_case_types)]
+            #visibility struct #name;
+
+            impl std::ops::Deref for #name {
+                type Target = #ty;
+
+                fn deref(&self) -> &#ty {
+                    #assert_sized
+
+                    std::thread_local! {
+                        static VALUE: std::cell::OnceCell<*mut #ty> = std::cell::OnceCell::new();
+                    }
+                    let ptr = VALUE.with(|cell| *cell.get_or_init(|| #init_ptr));
+                    unsafe { &*ptr }
+                }
+            }
+        };
+    }
+
+    // Assert that the static type implements Sync. If not, user sees an error
+    // message like the following. We span this assertion with the field type's
+    // line/column so that the error message appears in the correct place.
+    //
+    //     error[E0277]: the trait bound `*const (): std::marker::Sync` is not satisfied
+    //       --> src/main.rs:10:21
+    //        |
+    //     10 |     static ref PTR: *const () = &();
+    //        |                     ^^^^^^^^^ `*const ()` cannot be shared between threads safely
+    let assert_sync = quote_spanned! {ty.span()=>
+        struct _AssertSync where #ty: std::marker::Sync;
+    };
+
     let init_ptr = quote_spanned! {init.span()=>
         Box::into_raw(Box::new(#init))
     };
 
-    let expanded = quote! {
+    if args.drop {
+        // Unlike the default `OnceLock` path, this keeps the raw pointer
+        // around so the allocation it owns can be reclaimed. A `thread_local!`
+        // destructor is NOT safe for this: `VALUE` is shared by every thread,
+        // but a thread-local guard only fires when the one thread that
+        // happens to run it exits, which can easily happen while other
+        // threads still hold `&#ty` borrows or before `ONCE` is ever reset —
+        // reclaiming the allocation then is a use-after-free, and leaves
+        // later derefs on a null pointer. Teardown is instead registered
+        // with libc's `atexit`, which runs once, when the process exits
+        // normally (e.g. after `main` returns). Threads still running at
+        // that point are not stopped, and `drop_at_exit` does not
+        // synchronise with them: a deref racing or following the teardown
+        // reads a freed or null pointer.
+        // A proc-macro crate can only export macros, not regular items, so
+        // this expands to a direct `libc::atexit` call rather than a
+        // re-export: crates using `#[lazy(drop)]` need their own `libc`
+        // dependency (see the docs on the `lazy` attribute).
+        return quote! {
+            #[allow(non_camel_case_types)]
+            #visibility struct #name;
+
+            impl std::ops::Deref for #name {
+                type Target = #ty;
+
+                fn deref(&self) -> &#ty {
+                    #assert_sync
+                    #assert_sized
+
+                    static ONCE: std::sync::Once = std::sync::Once::new();
+                    static mut VALUE: *mut #ty = std::ptr::null_mut();
+
+                    extern "C" fn drop_at_exit() {
+                        unsafe {
+                            if !VALUE.is_null() {
+                                drop(Box::from_raw(VALUE));
+                                VALUE = std::ptr::null_mut();
+                            }
+                        }
+                    }
+
+                    unsafe {
+                        ONCE.call_once(|| {
+                            VALUE = #init_ptr;
+                            libc::atexit(drop_at_exit);
+                        });
+                        &*VALUE
+                    }
+                }
+            }
+        };
+    }
+
+    // Under `--cfg loom`, statics are re-run on every interleaving loom
+    // explores, so `static mut VALUE: *mut #ty` can't carry state across
+    // executions the way it does with `std::sync::Once`. `loom::lazy_static!`
+    // is loom's own replacement for this pattern: it gives every thread in
+    // the *same* execution a view of one shared, execution-scoped slot (and
+    // resets that slot between executions), so the one-time init it guards
+    // is genuinely visible across threads the way `std::sync::Once` is.
+    // A per-thread cell gated by a single global `Once` would not do this:
+    // only the first thread to run the closure would ever populate its own
+    // cell, leaving every other thread's slot null.
+    //
+    // `loom` isn't a cfg any downstream crate declares via `--check-cfg`, so
+    // `-D warnings` builds would trip `unexpected_cfgs` on every expansion
+    // of this macro. An `#[allow(unexpected_cfgs)]` attached directly beside
+    // a `#[cfg(...)]` on the very same item does NOT suppress the lint (it's
+    // raised while evaluating the `cfg` predicate itself, before item-level
+    // allows apply) — it only works attached to an *enclosing* item, hence
+    // wrapping both impls in an anonymous `const _: () = { ... };` block,
+    // which doesn't affect where the impls themselves apply.
+    quote! {
         #[allow(non_camel_case_types)]
         #visibility struct #name;
 
-        impl std::ops::Deref for #name {
-            type Target = #ty;
+        #[allow(unexpected_cfgs)]
+        const _: () = {
+            #[cfg(not(loom))]
+            impl std::ops::Deref for #name {
+                type Target = #ty;
+
+                fn deref(&self) -> &#ty {
+                    #assert_sync
+                    #assert_sized
+
+                    static VALUE: std::sync::OnceLock<#ty> = std::sync::OnceLock::new();
+                    VALUE.get_or_init(|| #init)
+                }
+            }
 
-            fn deref(&self) -> &#ty {
-                #assert_sync
-                #assert_sized
+            #[cfg(loom)]
+            impl std::ops::Deref for #name {
+                type Target = #ty;
 
-                static ONCE: std::sync::Once = std::sync::Once::new();
-                static mut VALUE: *mut #ty = 0 as *mut #ty;
+                fn deref(&self) -> &#ty {
+                    #assert_sync
+                    #assert_sized
 
-                unsafe {
-                    ONCE.call_once(|| VALUE = #init_ptr);
+                    loom::lazy_static! {
+                        static ref VALUE: #ty = #init;
+                    }
                     &*VALUE
                 }
             }
-        }
-    };
-
-    TokenStream::from(expanded)
+        };
+    }
 }
@@ -1,4 +1,4 @@
-use lazing::lazy;
+use lazing::{lazy, lazy_block};
 use std::ops::Deref;
 
 #[lazy]
@@ -9,6 +9,73 @@ fn it_works() {
     assert_eq!("123", I.deref());
 }
 
+lazy_block! {
+    static K: String = "789".to_owned();
+    // `ref` is only accepted here, inside `lazy_block!`: it's invalid static
+    // syntax, so rustc rejects `#[lazy] static ref ...;` before the
+    // attribute macro ever runs.
+    static ref L: u32 = 42;
+}
+#[test]
+fn it_works_with_lazy_block() {
+    assert_eq!("789", K.deref());
+    assert_eq!(&42, L.deref());
+}
+
+#[lazy(thread_local)]
+static M: std::cell::Cell<u32> = std::cell::Cell::new(0);
+#[test]
+fn it_works_with_thread_local() {
+    M.set(1);
+    assert_eq!(1, M.get());
+
+    std::thread::spawn(|| {
+        assert_eq!(0, M.get());
+        M.set(2);
+        assert_eq!(2, M.get());
+    })
+    .join()
+    .unwrap();
+
+    assert_eq!(1, M.get());
+}
+
+struct DropFlag;
+impl Drop for DropFlag {
+    fn drop(&mut self) {
+        DROPPED.store(true, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+static DROPPED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+#[lazy(drop)]
+static N: DropFlag = DropFlag;
+#[test]
+fn it_registers_drop_at_exit() {
+    // `N`'s destructor is registered with `libc::atexit` and only runs once
+    // this process exits, so this just checks initialization succeeds
+    // without running the destructor early.
+    let _ = N.deref();
+    assert!(!DROPPED.load(std::sync::atomic::Ordering::SeqCst));
+}
+
+#[lazy(drop)]
+static P: DropFlag = DropFlag;
+#[test]
+fn it_survives_the_initializing_thread_exiting() {
+    // `P` is shared across every thread, so initializing it on a thread that
+    // then exits must not tear down the value early: only a real
+    // process-exit hook (not a `thread_local!` destructor) can reclaim it
+    // safely.
+    std::thread::spawn(|| {
+        let _ = P.deref();
+    })
+    .join()
+    .unwrap();
+
+    let _ = P.deref();
+}
+
 fn get_type_name<T>(_: &T) -> &'static str {
     std::any::type_name::<T>()
 }